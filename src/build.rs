@@ -1,107 +1,29 @@
 use std::convert::TryInto;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{self, Command};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use fs_err as fs;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use xwin::util::ProgressTarget;
 
+use crate::msvc;
+use crate::toolchain;
+
 /// Compile a local package and all of its dependencies
 #[derive(Clone, Debug, Default, Parser)]
 #[clap(setting = clap::AppSettings::DeriveDisplayOrder, after_help = "Run `cargo help build` for more detailed information.")]
 pub struct Build {
-    /// Do not print cargo log messages
-    #[clap(short = 'q', long)]
-    pub quiet: bool,
-
-    /// Package to build (see `cargo help pkgid`)
-    #[clap(
-        short = 'p',
-        long = "package",
-        value_name = "SPEC",
-        multiple_values = true
-    )]
-    pub packages: Vec<String>,
-
-    /// Build all packages in the workspace
-    #[clap(long)]
-    pub workspace: bool,
-
-    /// Exclude packages from the build
-    #[clap(long, value_name = "SPEC", multiple_values = true)]
-    pub exclude: Vec<String>,
-
-    /// Alias for workspace (deprecated)
-    #[clap(long)]
-    pub all: bool,
-
     /// Number of parallel jobs, defaults to # of CPUs
     #[clap(short = 'j', long, value_name = "N")]
     pub jobs: Option<usize>,
 
-    /// Build only this package's library
-    #[clap(long)]
-    pub lib: bool,
-
-    /// Build only the specified binary
-    #[clap(long, value_name = "NAME", multiple_values = true)]
-    pub bin: Vec<String>,
-
-    /// Build all binaries
-    #[clap(long)]
-    pub bins: bool,
-
-    /// Build only the specified example
-    #[clap(long, value_name = "NAME", multiple_values = true)]
-    pub example: Vec<String>,
-
-    /// Build all examples
-    #[clap(long)]
-    pub examples: bool,
-
-    /// Build only the specified test target
-    #[clap(long, value_name = "NAME", multiple_values = true)]
-    pub test: Vec<String>,
-
-    /// Build all tests
-    #[clap(long)]
-    pub tests: bool,
-
-    /// Build only the specified bench target
-    #[clap(long, value_name = "NAME", multiple_values = true)]
-    pub bench: Vec<String>,
-
-    /// Build all benches
-    #[clap(long)]
-    pub benches: bool,
-
-    /// Build all targets
-    #[clap(long)]
-    pub all_targets: bool,
-
     /// Build artifacts in release mode, with optimizations
     #[clap(short = 'r', long)]
     pub release: bool,
 
-    /// Build artifacts with the specified Cargo profile
-    #[clap(long, value_name = "PROFILE-NAME")]
-    pub profile: Option<String>,
-
-    /// Space or comma separated list of features to activate
-    #[clap(long, multiple_values = true)]
-    pub features: Vec<String>,
-
-    /// Activate all available features
-    #[clap(long)]
-    pub all_features: bool,
-
-    /// Do not activate the `default` feature
-    #[clap(long)]
-    pub no_default_features: bool,
-
     /// Build for the target triple
     #[clap(long, value_name = "TRIPLE", env = "CARGO_BUILD_TARGET")]
     pub target: Option<String>,
@@ -110,61 +32,14 @@ pub struct Build {
     #[clap(long, value_name = "DIRECTORY", parse(from_os_str))]
     pub target_dir: Option<PathBuf>,
 
-    /// Copy final artifacts to this directory (unstable)
-    #[clap(long, value_name = "PATH", parse(from_os_str))]
-    pub out_dir: Option<PathBuf>,
-
     /// Path to Cargo.toml
     #[clap(long, value_name = "PATH", parse(from_os_str))]
     pub manifest_path: Option<PathBuf>,
 
-    /// Ignore `rust-version` specification in packages
-    #[clap(long)]
-    pub ignore_rust_version: bool,
-
-    /// Error format
-    #[clap(long, value_name = "FMT", multiple_values = true)]
-    pub message_format: Vec<String>,
-
-    /// Output the build plan in JSON (unstable)
-    #[clap(long)]
-    pub build_plan: bool,
-
-    /// Output build graph in JSON (unstable)
-    #[clap(long)]
-    pub unit_graph: bool,
-
-    /// Outputs a future incompatibility report at the end of the build (unstable)
-    #[clap(long)]
-    pub future_incompat_report: bool,
-
-    /// Use verbose output (-vv very verbose/build.rs output)
-    #[clap(short = 'v', long, parse(from_occurrences), max_occurrences = 2)]
-    pub verbose: usize,
-
-    /// Coloring: auto, always, never
-    #[clap(long, value_name = "WHEN")]
-    pub color: Option<String>,
-
-    /// Require Cargo.lock and cache are up to date
-    #[clap(long)]
-    pub frozen: bool,
-
-    /// Require Cargo.lock is up to date
-    #[clap(long)]
-    pub locked: bool,
-
-    /// Run without accessing the network
-    #[clap(long)]
-    pub offline: bool,
-
-    /// Override a configuration value (unstable)
-    #[clap(long, value_name = "KEY=VALUE", multiple_values = true)]
-    pub config: Vec<String>,
-
-    /// Unstable (nightly-only) flags to Cargo, see 'cargo -Z help' for details
-    #[clap(short = 'Z', value_name = "FLAG", multiple_values = true)]
-    pub unstable_flags: Vec<String>,
+    /// Any other `cargo <subcommand>` arguments, forwarded verbatim after a `--`
+    /// separator (e.g. `-- --package foo --features bar --timings`)
+    #[clap(last = true, value_name = "ARGS", multiple_values = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
 
     /// xwin cache directory
     #[clap(long, parse(from_os_str), env = "XWIN_CACHE_DIR", hide = true)]
@@ -196,6 +71,24 @@ pub struct Build {
     /// a "<major>.<minor>" version.
     #[clap(long, env = "XWIN_VERSION", default_value = "16", hide = true)]
     pub xwin_version: String,
+
+    /// Download a pinned prebuilt LLVM release into the xwin cache dir if no
+    /// usable clang-cl/lld-link/llvm-lib/llvm-dlltool toolchain can be found
+    #[clap(long, hide = true)]
+    pub download_llvm: bool,
+
+    /// Use an already-installed MSVC CRT/SDK instead of downloading one via xwin;
+    /// the build fails if no such install can be found
+    #[clap(long, hide = true)]
+    pub xwin_use_system: bool,
+
+    /// Root of an existing MSVC/Windows SDK install to use instead of downloading one
+    #[clap(long, value_name = "PATH", parse(from_os_str), hide = true)]
+    pub msvc_root: Option<PathBuf>,
+
+    /// Number of times to retry a payload download after a transient network failure
+    #[clap(long, value_name = "N", default_value = "3", hide = true)]
+    pub xwin_download_retries: u32,
 }
 
 impl Build {
@@ -210,181 +103,119 @@ impl Build {
         Ok(())
     }
 
-    /// Generate cargo subcommand
-    pub fn build_command(&self, subcommand: &str) -> Result<Command> {
-        let xwin_cache_dir = self.xwin_cache_dir.clone().unwrap_or_else(|| {
+    /// The xwin cache directory to use, honoring `--xwin-cache-dir`/`XWIN_CACHE_DIR`
+    pub fn resolved_xwin_cache_dir(&self) -> PathBuf {
+        self.xwin_cache_dir.clone().unwrap_or_else(|| {
             dirs::cache_dir()
                 // If the really is no cache dir, cwd will also do
                 .unwrap_or_else(|| env::current_dir().expect("Failed to get current dir"))
                 .join(env!("CARGO_PKG_NAME"))
                 .join("xwin")
-        });
+        })
+    }
+
+    /// Generate cargo subcommand
+    pub fn build_command(&self, subcommand: &str) -> Result<Command> {
+        let xwin_cache_dir = self.resolved_xwin_cache_dir();
         fs::create_dir_all(&xwin_cache_dir)?;
 
         let mut build = Command::new("cargo");
         build.arg(subcommand);
 
-        // collect cargo build arguments
-        if self.quiet {
-            build.arg("--quiet");
-        }
-        for pkg in &self.packages {
-            build.arg("--package").arg(pkg);
-        }
-        if self.workspace {
-            build.arg("--workspace");
-        }
-        for item in &self.exclude {
-            build.arg("--excude").arg(item);
-        }
-        if self.all {
-            build.arg("--all");
-        }
+        // Re-emit the flags we intercept for our own env setup, then forward
+        // everything else (packages, features, profile, `--timings`, ...) verbatim.
         if let Some(jobs) = self.jobs {
             build.arg("--jobs").arg(jobs.to_string());
         }
-        if self.lib {
-            build.arg("--lib");
-        }
-        for bin in &self.bin {
-            build.arg("--bin").arg(bin);
-        }
-        if self.bins {
-            build.arg("--bins");
-        }
-        for example in &self.example {
-            build.arg("--example").arg(example);
-        }
-        if self.examples {
-            build.arg("--examples");
-        }
-        for test in &self.test {
-            build.arg("--test").arg(test);
-        }
-        if self.tests {
-            build.arg("--tests");
-        }
-        for bench in &self.bench {
-            build.arg("--bench").arg(bench);
-        }
-        if self.benches {
-            build.arg("--benches");
-        }
-        if self.all_targets {
-            build.arg("--all-targets");
-        }
         if self.release {
             build.arg("--release");
         }
-        if let Some(profile) = self.profile.as_ref() {
-            build.arg("--profile").arg(profile);
-        }
-        for feature in &self.features {
-            build.arg("--features").arg(feature);
-        }
-        if self.all_features {
-            build.arg("--all-features");
-        }
-        if self.no_default_features {
-            build.arg("--no-default-features");
-        }
         if let Some(target) = self.target.as_ref() {
             build.arg("--target").arg(target);
         }
         if let Some(dir) = self.target_dir.as_ref() {
             build.arg("--target-dir").arg(dir);
         }
-        if let Some(dir) = self.out_dir.as_ref() {
-            build.arg("--out-dir").arg(dir);
-        }
         if let Some(path) = self.manifest_path.as_ref() {
             build.arg("--manifest-path").arg(path);
         }
-        if self.ignore_rust_version {
-            build.arg("--ignore-rust-version");
-        }
-        for fmt in &self.message_format {
-            build.arg("--message-format").arg(fmt);
-        }
-        if self.build_plan {
-            build.arg("--build-plan");
-        }
-        if self.unit_graph {
-            build.arg("--unit-graph");
-        }
-        if self.future_incompat_report {
-            build.arg("--future-incompat-report");
-        }
-        if self.verbose > 0 {
-            build.arg(format!("-{}", "v".repeat(self.verbose)));
-        }
-        if let Some(color) = self.color.as_ref() {
-            build.arg("--color").arg(color);
-        }
-        if self.frozen {
-            build.arg("--frozen");
-        }
-        if self.locked {
-            build.arg("--locked");
-        }
-        if self.offline {
-            build.arg("--offline");
-        }
-        for config in &self.config {
-            build.arg("--config").arg(config);
-        }
-        for flag in &self.unstable_flags {
-            build.arg("-Z").arg(flag);
-        }
+        build.args(&self.args);
 
         if let Some(target) = self.target.as_ref() {
             if target.contains("msvc") {
-                self.setup_msvc_crt(xwin_cache_dir.clone())?;
+                let toolchain = toolchain::discover(&xwin_cache_dir, self.download_llvm)?;
+                let clang_cl = toolchain.clang_cl.display();
                 let env_target = target.to_uppercase().replace('-', "_");
-                build.env("TARGET_CC", format!("clang-cl --target={}", target));
-                build.env("TARGET_CXX", format!("clang-cl --target={}", target));
+                build.env("TARGET_CC", format!("{} --target={}", clang_cl, target));
+                build.env("TARGET_CXX", format!("{} --target={}", clang_cl, target));
                 build.env(
                     format!("CC_{}", env_target.to_lowercase()),
-                    format!("clang-cl --target={}", target),
+                    format!("{} --target={}", clang_cl, target),
                 );
                 build.env(
                     format!("CXX_{}", env_target.to_lowercase()),
-                    format!("clang-cl --target={}", target),
+                    format!("{} --target={}", clang_cl, target),
                 );
-                build.env("TARGET_AR", "llvm-lib");
-                build.env(format!("AR_{}", env_target), "llvm-lib");
-                build.env(format!("CARGO_TARGET_{}_LINKER", env_target), "lld-link");
-
-                let cl_flags = format!(
-                    "-fuse-ld=lld-link /imsvc{dir}/crt/include /imsvc{dir}/sdk/include/ucrt /imsvc{dir}/sdk/include/um /imsvc{dir}/sdk/include/shared",
-                    dir = xwin_cache_dir.display()
+                build.env("TARGET_AR", &toolchain.llvm_lib);
+                build.env(format!("AR_{}", env_target), &toolchain.llvm_lib);
+                build.env(
+                    format!("CARGO_TARGET_{}_LINKER", env_target),
+                    &toolchain.lld_link,
                 );
-                build.env("CL_FLAGS", &cl_flags);
-                build.env(format!("CFLAGS_{}", env_target.to_lowercase()), &cl_flags);
-                build.env(format!("CXXFLAGS_{}", env_target.to_lowercase()), &cl_flags);
 
-                let target_arch = target
+                let target_arch: xwin::Arch = target
                     .split_once('-')
                     .map(|(x, _)| x)
-                    .context("invalid target triple")?;
-                let rustflags = format!(
-                    "-Lnative={dir}/crt/lib/{arch} -Lnative={dir}/sdk/lib/um/{arch} -Lnative={dir}/sdk/lib/ucrt/{arch}",
-                    dir = xwin_cache_dir.display(),
-                    arch = target_arch,
-                );
+                    .context("invalid target triple")?
+                    .parse()
+                    .context("invalid target architecture")?;
+
+                let system_msvc = if self.xwin_use_system || self.msvc_root.is_some() {
+                    msvc::detect(self.msvc_root.as_deref(), target_arch)
+                } else {
+                    None
+                };
+
+                let (cl_flags, rustflags) = if let Some(system_msvc) = system_msvc {
+                    (system_msvc.cl_flags(), system_msvc.rustflags())
+                } else {
+                    if self.xwin_use_system {
+                        bail!(
+                            "--xwin-use-system was passed but no MSVC/SDK install could be found; \
+                             set VCINSTALLDIR/WindowsSdkDir or pass --msvc-root"
+                        );
+                    }
+                    if let Some(msvc_root) = &self.msvc_root {
+                        bail!(
+                            "--msvc-root {} was passed but no MSVC/SDK install could be found under it",
+                            msvc_root.display()
+                        );
+                    }
+                    self.setup_msvc_crt(xwin_cache_dir.clone())?;
+                    (
+                        format!(
+                            "/imsvc{dir}/crt/include /imsvc{dir}/sdk/include/ucrt /imsvc{dir}/sdk/include/um /imsvc{dir}/sdk/include/shared",
+                            dir = xwin_cache_dir.display()
+                        ),
+                        format!(
+                            "-Lnative={dir}/crt/lib/{arch} -Lnative={dir}/sdk/lib/um/{arch} -Lnative={dir}/sdk/lib/ucrt/{arch}",
+                            dir = xwin_cache_dir.display(),
+                            arch = target_arch.as_str(),
+                        ),
+                    )
+                };
+                let cl_flags = format!("-fuse-ld={} {}", toolchain.lld_link.display(), cl_flags);
+
+                build.env("CL_FLAGS", &cl_flags);
+                build.env(format!("CFLAGS_{}", env_target.to_lowercase()), &cl_flags);
+                build.env(format!("CXXFLAGS_{}", env_target.to_lowercase()), &cl_flags);
                 build.env(format!("CARGO_TARGET_{}_RUSTFLAGS", env_target), rustflags);
 
-                #[cfg(target_os = "macos")]
-                if let Ok(path) = env::var("PATH") {
-                    let mut new_path = path.clone();
-                    if cfg!(target_arch = "x86_64") && !path.contains("/usr/local/opt/llvm/bin") {
-                        new_path.push_str(":/usr/local/opt/llvm/bin");
-                    } else if cfg!(target_arch = "aarch64")
-                        && !path.contains("/opt/homebrew/opt/llvm/bin")
-                    {
-                        new_path.push_str(":/opt/homebrew/opt/llvm/bin");
-                    }
-                    build.env("PATH", new_path);
+                let path = env::var("PATH").unwrap_or_default();
+                if !env::split_paths(&path).any(|dir| dir == toolchain.bin_dir) {
+                    let mut paths: Vec<PathBuf> = vec![toolchain.bin_dir.clone()];
+                    paths.extend(env::split_paths(&path));
+                    build.env("PATH", env::join_paths(paths)?);
                 }
             }
         }
@@ -393,10 +224,8 @@ impl Build {
     }
 
     fn setup_msvc_crt(&self, cache_dir: PathBuf) -> Result<()> {
-        let done_mark_file = cache_dir.join("DONE");
-        if done_mark_file.is_file() {
-            return Ok(());
-        }
+        let manifest_file = cache_dir.join("payloads.manifest");
+        let mut completed = read_payload_manifest(&manifest_file)?;
 
         let draw_target = ProgressTarget::Stdout;
         let ctx = if self.xwin_cache_dir.is_some() {
@@ -416,63 +245,79 @@ impl Build {
             .iter()
             .fold(0, |acc, var| acc | *var as u32);
         let pruned = xwin::prune_pkg_list(&pkg_manifest, arches, variants)?;
-        let op = xwin::Ops::Splat(xwin::SplatConfig {
-            include_debug_libs: false,
-            include_debug_symbols: false,
-            enable_symlinks: !cfg!(target_os = "macos"),
-            preserve_ms_arch_notation: false,
-            copy: false,
-            output: cache_dir.clone().try_into()?,
-        });
-        let pkgs = pkg_manifest.packages;
+
+        // Skip payloads whose checksum we've already fetched and splatted successfully,
+        // so an interrupted run resumes instead of re-downloading everything.
+        let pending: Vec<_> = pruned
+            .into_iter()
+            .filter(|pay| completed.get(&payload_key(pay)) != Some(&pay.sha256.to_string()))
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let jobs = self.jobs.unwrap_or_else(|| {
+            env::var("NUM_JOBS")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+                .unwrap_or(1)
+        }).max(1);
 
         let mp = MultiProgress::with_draw_target(draw_target.into());
-        let work_items: Vec<_> = pruned
-        .into_iter()
-        .map(|pay| {
-            let prefix = match pay.kind {
-                xwin::PayloadKind::CrtHeaders => "CRT.headers".to_owned(),
-                xwin::PayloadKind::CrtLibs => {
-                    format!(
-                        "CRT.libs.{}.{}",
-                        pay.target_arch.map(|ta| ta.as_str()).unwrap_or("all"),
-                        pay.variant.map(|v| v.as_str()).unwrap_or("none")
-                    )
-                }
-                xwin::PayloadKind::SdkHeaders => {
-                    format!(
-                        "SDK.headers.{}.{}",
-                        pay.target_arch.map(|v| v.as_str()).unwrap_or("all"),
-                        pay.variant.map(|v| v.as_str()).unwrap_or("none")
-                    )
-                }
-                xwin::PayloadKind::SdkLibs => {
-                    format!(
-                        "SDK.libs.{}",
-                        pay.target_arch.map(|ta| ta.as_str()).unwrap_or("all")
-                    )
+        mp.set_move_cursor(true);
+
+        // Bound the number of payloads in flight at once to `jobs`, committing the
+        // manifest after each batch so a kill/crash mid-run only loses that batch.
+        for batch in pending.chunks(jobs) {
+            let work_items: Vec<_> = batch
+                .iter()
+                .cloned()
+                .map(|pay| {
+                    let pb = mp.add(
+                        ProgressBar::with_draw_target(0, draw_target.into())
+                            .with_prefix(payload_prefix(&pay))
+                            .with_style(
+                                ProgressStyle::default_bar()
+                                    .template("{spinner:.green} {prefix:.bold} [{elapsed}] {wide_bar:.green} {bytes}/{total_bytes} {msg}").unwrap()
+                                    .progress_chars("=> "),
+                            ),
+                    );
+                    xwin::WorkItem {
+                        payload: std::sync::Arc::new(pay),
+                        progress: pb,
+                    }
+                })
+                .collect();
+
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                // `xwin::Ctx::execute` takes `self: Arc<Self>`, `packages` and `ops` by
+                // value, and neither `ManifestItem` nor `xwin::Ops` implements `Clone` —
+                // so each attempt rebuilds its own fresh, cheap-to-construct copies
+                // instead of trying to reuse the ones from a previous attempt.
+                let packages = self.load_manifest(&ctx, draw_target)?.packages;
+                let op = self.splat_op(&cache_dir)?;
+                match ctx.clone().execute(packages, work_items.clone(), arches, variants, op) {
+                    Ok(()) => break,
+                    Err(err) if attempt <= self.xwin_download_retries => {
+                        eprintln!(
+                            "payload batch failed (attempt {}/{}): {}, retrying",
+                            attempt,
+                            self.xwin_download_retries + 1,
+                            err
+                        );
+                    }
+                    Err(err) => return Err(err.into()),
                 }
-                xwin::PayloadKind::SdkStoreLibs => "SDK.libs.store.all".to_owned(),
-                xwin::PayloadKind::Ucrt => "SDK.ucrt.all".to_owned(),
-            };
-
-            let pb = mp.add(
-                ProgressBar::with_draw_target(0, draw_target.into()).with_prefix(prefix).with_style(
-                    ProgressStyle::default_bar()
-                        .template("{spinner:.green} {prefix:.bold} [{elapsed}] {wide_bar:.green} {bytes}/{total_bytes} {msg}").unwrap()
-                        .progress_chars("=> "),
-                ),
-            );
-            xwin::WorkItem {
-                payload: std::sync::Arc::new(pay),
-                progress: pb,
             }
-        })
-        .collect();
 
-        mp.set_move_cursor(true);
-        ctx.execute(pkgs, work_items, arches, variants, op)?;
-        fs::write(done_mark_file, "")?;
+            for pay in batch {
+                completed.insert(payload_key(pay), pay.sha256.to_string());
+            }
+            write_payload_manifest(&manifest_file, &completed)?;
+        }
 
         let dl = cache_dir.join("dl");
         if dl.exists() {
@@ -508,4 +353,112 @@ impl Build {
         manifest_pb.finish_with_message("📥 downloaded");
         Ok(pkg_manifest)
     }
+
+    /// A fresh splat configuration, cheap to construct on every retry attempt
+    /// since `xwin::Ops` doesn't implement `Clone`.
+    fn splat_op(&self, cache_dir: &Path) -> Result<xwin::Ops> {
+        Ok(xwin::Ops::Splat(xwin::SplatConfig {
+            include_debug_libs: false,
+            include_debug_symbols: false,
+            enable_symlinks: !cfg!(target_os = "macos"),
+            preserve_ms_arch_notation: false,
+            copy: false,
+            output: cache_dir.to_owned().try_into()?,
+        }))
+    }
+}
+
+/// Stable identity for a payload within the pruned package list, used as the
+/// manifest key to tell "already splatted" payloads apart across runs.
+fn payload_key(pay: &xwin::Payload) -> String {
+    format!(
+        "{:?}.{}.{}",
+        pay.kind,
+        pay.target_arch.map(|ta| ta.as_str()).unwrap_or("all"),
+        pay.variant.map(|v| v.as_str()).unwrap_or("none"),
+    )
+}
+
+fn payload_prefix(pay: &xwin::Payload) -> String {
+    match pay.kind {
+        xwin::PayloadKind::CrtHeaders => "CRT.headers".to_owned(),
+        xwin::PayloadKind::CrtLibs => {
+            format!(
+                "CRT.libs.{}.{}",
+                pay.target_arch.map(|ta| ta.as_str()).unwrap_or("all"),
+                pay.variant.map(|v| v.as_str()).unwrap_or("none")
+            )
+        }
+        xwin::PayloadKind::SdkHeaders => {
+            format!(
+                "SDK.headers.{}.{}",
+                pay.target_arch.map(|v| v.as_str()).unwrap_or("all"),
+                pay.variant.map(|v| v.as_str()).unwrap_or("none")
+            )
+        }
+        xwin::PayloadKind::SdkLibs => {
+            format!(
+                "SDK.libs.{}",
+                pay.target_arch.map(|ta| ta.as_str()).unwrap_or("all")
+            )
+        }
+        xwin::PayloadKind::SdkStoreLibs => "SDK.libs.store.all".to_owned(),
+        xwin::PayloadKind::Ucrt => "SDK.ucrt.all".to_owned(),
+    }
+}
+
+/// Load the `payload_key -> sha256` manifest recorded by a previous (possibly
+/// interrupted) `setup_msvc_crt` run, one `key\tsha256` pair per line.
+fn read_payload_manifest(path: &std::path::Path) -> Result<std::collections::HashMap<String, String>> {
+    let mut completed = std::collections::HashMap::new();
+    if !path.is_file() {
+        return Ok(completed);
+    }
+    for line in fs::read_to_string(path)?.lines() {
+        if let Some((key, sha)) = line.split_once('\t') {
+            completed.insert(key.to_owned(), sha.to_owned());
+        }
+    }
+    Ok(completed)
+}
+
+fn write_payload_manifest(
+    path: &std::path::Path,
+    completed: &std::collections::HashMap<String, String>,
+) -> Result<()> {
+    let mut contents = String::new();
+    for (key, sha) in completed {
+        contents.push_str(key);
+        contents.push('\t');
+        contents.push_str(sha);
+        contents.push('\n');
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_payload_manifest, write_payload_manifest};
+
+    #[test]
+    fn missing_manifest_reads_as_empty() {
+        let path = std::env::temp_dir().join("cargo-xwinbuild-test-missing.manifest");
+        let completed = read_payload_manifest(&path).unwrap();
+        assert!(completed.is_empty());
+    }
+
+    #[test]
+    fn manifest_round_trips_through_write_and_read() {
+        let path = std::env::temp_dir().join("cargo-xwinbuild-test-roundtrip.manifest");
+        let mut completed = std::collections::HashMap::new();
+        completed.insert("CrtLibs.x86_64.desktop".to_owned(), "abc123".to_owned());
+        completed.insert("SdkHeaders.all.none".to_owned(), "def456".to_owned());
+
+        write_payload_manifest(&path, &completed).unwrap();
+        let read_back = read_payload_manifest(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(read_back, completed);
+    }
 }
@@ -0,0 +1,378 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use fs_err as fs;
+
+use crate::build::Build;
+use crate::toolchain;
+
+/// Compile a local package and produce a consumable Windows C library
+///
+/// Mirrors `cargo-c`'s `cbuild`: after the normal cargo build, a C header is
+/// generated via cbindgen, a `.def` file is synthesized from the crate's
+/// exported symbols, an MSVC import library is built with the
+/// cross-provisioned LLVM toolchain, and a pkg-config file is written.
+#[derive(Clone, Debug, Default, Parser)]
+#[clap(setting = clap::AppSettings::DeriveDisplayOrder, after_help = "Run `cargo help build` for more detailed information.")]
+pub struct Cbuild {
+    #[clap(flatten)]
+    pub build: Build,
+
+    /// Directory the generated header, .def, import library and .pc file are written to
+    #[clap(long, value_name = "DIRECTORY", parse(from_os_str))]
+    pub capi_dir: Option<PathBuf>,
+
+    /// Name of the generated header (defaults to the crate name)
+    #[clap(long, value_name = "NAME")]
+    pub header_name: Option<String>,
+
+    /// Path to a cbindgen.toml to use instead of the crate's own `cbindgen.toml`
+    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    pub cbindgen_config: Option<PathBuf>,
+}
+
+impl Cbuild {
+    /// Execute `cargo build` and generate the C-ABI artifacts it implies
+    pub fn execute(&self) -> Result<()> {
+        let target = self
+            .build
+            .target
+            .as_deref()
+            .context("cbuild requires --target <TRIPLE> to produce a Windows C library")?;
+        if !target.ends_with("-windows-msvc") {
+            bail!(
+                "cbuild requires a *-windows-msvc --target to produce a Windows C library, got `{}`",
+                target
+            );
+        }
+        self.check_cdylib_or_staticlib()?;
+
+        self.run_cargo_build()?;
+
+        let crate_name = self.crate_name()?;
+        let out_dir = self.capi_dir.clone().unwrap_or_else(|| self.artifact_dir(target));
+        fs::create_dir_all(&out_dir)?;
+
+        let header = self.generate_header(&crate_name, &out_dir)?;
+        let dll = self.artifact_dir(target).join(format!("{}.dll", crate_name));
+        let def_file = self.generate_def_file(&crate_name, &out_dir)?;
+        let implib = self.generate_import_library(&crate_name, target, &def_file, &dll, &out_dir)?;
+        self.generate_pkg_config(&crate_name, &header, &implib, &out_dir)?;
+        Ok(())
+    }
+
+    fn run_cargo_build(&self) -> Result<()> {
+        let mut build = self.build.build_command("build")?;
+        let mut child = build.spawn().context("Failed to run cargo build")?;
+        let status = child.wait().expect("Failed to wait on cargo build process");
+        if !status.success() {
+            process::exit(status.code().unwrap_or(1));
+        }
+        Ok(())
+    }
+
+    /// Directory cargo places target artifacts in for the given triple
+    fn artifact_dir(&self, target: &str) -> PathBuf {
+        let mut dir = self
+            .build
+            .target_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("target"));
+        dir.push(target);
+        dir.push(if self.build.release { "release" } else { "debug" });
+        dir
+    }
+
+    fn crate_name(&self) -> Result<String> {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("pkgid");
+        if let Some(manifest_path) = self.build.manifest_path.as_ref() {
+            cmd.arg("--manifest-path").arg(manifest_path);
+        }
+        let output = cmd.output().context("Failed to run `cargo pkgid`")?;
+        if !output.status.success() {
+            bail!("`cargo pkgid` failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        let pkgid = String::from_utf8(output.stdout)?;
+        parse_pkgid(pkgid.trim())
+    }
+
+    /// Fail fast if the crate doesn't build a `cdylib`/`staticlib`, instead of running
+    /// a full cargo build only to fail deep inside `llvm-readobj`/`llvm-dlltool` on
+    /// whatever artifact a plain `bin`/`rlib` crate happened to produce.
+    fn check_cdylib_or_staticlib(&self) -> Result<()> {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("locate-project").arg("--message-format").arg("plain");
+        if let Some(manifest_path) = self.build.manifest_path.as_ref() {
+            cmd.arg("--manifest-path").arg(manifest_path);
+        }
+        let output = cmd.output().context("Failed to run `cargo locate-project`")?;
+        if !output.status.success() {
+            bail!(
+                "`cargo locate-project` failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let manifest_path = String::from_utf8(output.stdout)?;
+        let manifest = fs::read_to_string(manifest_path.trim())?;
+        if !manifest.contains("cdylib") && !manifest.contains("staticlib") {
+            bail!(
+                "cbuild requires the crate's `[lib]` to declare `crate-type = [\"cdylib\"]` \
+                 (or `\"staticlib\"`) to produce a Windows C library"
+            );
+        }
+        Ok(())
+    }
+
+    /// Generate the C header via cbindgen
+    fn generate_header(&self, crate_name: &str, out_dir: &Path) -> Result<PathBuf> {
+        let header_name = self
+            .header_name
+            .clone()
+            .unwrap_or_else(|| format!("{}.h", crate_name));
+        let header = out_dir.join(&header_name);
+
+        let mut cmd = Command::new("cbindgen");
+        if let Some(manifest_path) = self.build.manifest_path.as_ref() {
+            cmd.arg("--manifest-path").arg(manifest_path);
+        }
+        if let Some(config) = self.cbindgen_config.as_ref() {
+            cmd.arg("--config").arg(config);
+        }
+        cmd.arg("--output").arg(&header);
+        let status = cmd
+            .status()
+            .context("Failed to run cbindgen, is it installed? (`cargo install cbindgen`)")?;
+        if !status.success() {
+            bail!("cbindgen failed to generate {}", header.display());
+        }
+        Ok(header)
+    }
+
+    /// Synthesize a module-definition file listing the crate's exported symbols
+    ///
+    /// A final linked PE image keeps its exports in the export directory, not in
+    /// a COFF symbol table, so this reads `llvm-readobj --coff-exports` rather
+    /// than `llvm-nm` (which only sees symbols still present for object files).
+    fn generate_def_file(&self, crate_name: &str, out_dir: &Path) -> Result<PathBuf> {
+        let target = self.build.target.as_deref().unwrap();
+        let dll = self.artifact_dir(target).join(format!("{}.dll", crate_name));
+        let toolchain = toolchain::discover(&self.build.resolved_xwin_cache_dir(), self.build.download_llvm)?;
+        let output = Command::new("llvm-readobj")
+            .env("PATH", toolchain.env_path()?)
+            .arg("--coff-exports")
+            .arg(&dll)
+            .output()
+            .context("Failed to run llvm-readobj to list exported symbols")?;
+        if !output.status.success() {
+            bail!("llvm-readobj failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let symbols: Vec<&str> = stdout
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("Name: "))
+            .filter(|sym| !sym.is_empty())
+            .collect();
+
+        let def_file = out_dir.join(format!("{}.def", crate_name));
+        let mut contents = format!("LIBRARY {}\nEXPORTS\n", crate_name);
+        for symbol in symbols {
+            contents.push_str("    ");
+            contents.push_str(symbol);
+            contents.push('\n');
+        }
+        fs::write(&def_file, contents)?;
+        Ok(def_file)
+    }
+
+    /// Synthesize an MSVC import library for the produced DLL using the cross toolchain
+    fn generate_import_library(
+        &self,
+        crate_name: &str,
+        target: &str,
+        def_file: &Path,
+        dll: &Path,
+        out_dir: &Path,
+    ) -> Result<PathBuf> {
+        let implib = out_dir.join(format!("{}.lib", crate_name));
+        let machine = match target.split_once('-').map(|(arch, _)| arch) {
+            Some("x86_64") => "i386:x86-64",
+            Some("aarch64") => "arm64",
+            Some("i686" | "i586") => "i386",
+            _ => bail!("Unsupported target architecture in {}", target),
+        };
+
+        let toolchain = toolchain::discover(&self.build.resolved_xwin_cache_dir(), self.build.download_llvm)?;
+        let status = Command::new(&toolchain.llvm_dlltool)
+            .arg("-m")
+            .arg(machine)
+            .arg("-d")
+            .arg(def_file)
+            .arg("-l")
+            .arg(&implib)
+            .arg("-D")
+            .arg(dll)
+            .status()
+            .with_context(|| format!("Failed to run {}", toolchain.llvm_dlltool.display()))?;
+        if !status.success() {
+            bail!("llvm-dlltool failed to generate {}", implib.display());
+        }
+        Ok(implib)
+    }
+
+    /// Emit a pkg-config `.pc` file describing how to link against the generated library
+    fn generate_pkg_config(
+        &self,
+        crate_name: &str,
+        header: &Path,
+        implib: &Path,
+        out_dir: &Path,
+    ) -> Result<PathBuf> {
+        let version = env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.0.0".to_owned());
+        let pc_file = out_dir.join(format!("{}.pc", crate_name));
+        let contents = format!(
+            "includedir={include_dir}\nlibdir={lib_dir}\n\nName: {name}\nDescription: {name} C library\nVersion: {version}\nLibs: -L${{libdir}} -l{name}\nCflags: -I${{includedir}}\n",
+            include_dir = header.parent().unwrap_or(out_dir).display(),
+            lib_dir = implib.parent().unwrap_or(out_dir).display(),
+            name = crate_name,
+            version = version,
+        );
+        fs::write(&pc_file, contents)?;
+        Ok(pc_file)
+    }
+}
+
+/// Lay out the `cbuild` artifacts under a `--prefix`/`--libdir`/`--includedir` tree
+#[derive(Clone, Debug, Default, Parser)]
+#[clap(setting = clap::AppSettings::DeriveDisplayOrder, after_help = "Run `cargo help build` for more detailed information.")]
+pub struct Cinstall {
+    #[clap(flatten)]
+    pub cbuild: Cbuild,
+
+    /// Installation prefix
+    #[clap(long, value_name = "DIRECTORY", parse(from_os_str), default_value = "/usr/local")]
+    pub prefix: PathBuf,
+
+    /// Directory the import library is installed to (relative to prefix unless absolute)
+    #[clap(long, value_name = "DIRECTORY", parse(from_os_str))]
+    pub libdir: Option<PathBuf>,
+
+    /// Directory the generated header is installed to (relative to prefix unless absolute)
+    #[clap(long, value_name = "DIRECTORY", parse(from_os_str))]
+    pub includedir: Option<PathBuf>,
+
+    /// Stage the install tree under this directory instead of writing directly into --prefix
+    #[clap(long, value_name = "DIRECTORY", parse(from_os_str))]
+    pub destdir: Option<PathBuf>,
+}
+
+impl Cinstall {
+    /// Build the C-ABI artifacts and install them under the requested prefix
+    pub fn execute(&self) -> Result<()> {
+        self.cbuild.execute()?;
+
+        let target = self
+            .cbuild
+            .build
+            .target
+            .as_deref()
+            .context("cinstall requires --target <TRIPLE>")?;
+        let crate_name = self.cbuild.crate_name()?;
+        let capi_dir = self
+            .cbuild
+            .capi_dir
+            .clone()
+            .unwrap_or_else(|| self.cbuild.artifact_dir(target));
+
+        let libdir = self.resolve_dir(self.libdir.clone().unwrap_or_else(|| PathBuf::from("lib")));
+        let includedir = self.resolve_dir(
+            self.includedir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("include")),
+        );
+        let pkgconfigdir = libdir.join("pkgconfig");
+
+        fs::create_dir_all(&libdir)?;
+        fs::create_dir_all(&includedir)?;
+        fs::create_dir_all(&pkgconfigdir)?;
+
+        let dll = self.cbuild.artifact_dir(target).join(format!("{}.dll", crate_name));
+        copy_into(&dll, &libdir)?;
+        copy_into(&capi_dir.join(format!("{}.lib", crate_name)), &libdir)?;
+        copy_into(
+            &capi_dir.join(
+                self.cbuild
+                    .header_name
+                    .clone()
+                    .unwrap_or_else(|| format!("{}.h", crate_name)),
+            ),
+            &includedir,
+        )?;
+        copy_into(&capi_dir.join(format!("{}.pc", crate_name)), &pkgconfigdir)?;
+        Ok(())
+    }
+
+    fn resolve_dir(&self, dir: PathBuf) -> PathBuf {
+        let joined = if dir.is_absolute() {
+            dir
+        } else {
+            self.prefix.join(dir)
+        };
+        match self.destdir.as_ref() {
+            Some(destdir) => destdir.join(joined.strip_prefix("/").unwrap_or(&joined)),
+            None => joined,
+        }
+    }
+}
+
+/// Derive a crate name from `cargo pkgid` output, e.g. `file:///path/to/pkg#name@1.0.0`
+/// or, for single-package manifests without an explicit name component, `file:///path/to/pkg#1.0.0`
+fn parse_pkgid(pkgid: &str) -> Result<String> {
+    let after_hash = pkgid.rsplit('#').next().unwrap_or(pkgid);
+    let name = after_hash.split('@').next().unwrap_or(after_hash);
+    if name.chars().next().map_or(false, |c| c.is_ascii_digit()) {
+        // No explicit name component, fall back to the last path segment
+        let path = pkgid.split('#').next().unwrap_or(pkgid);
+        Ok(Path::new(path)
+            .file_name()
+            .context("Failed to determine crate name from cargo pkgid")?
+            .to_string_lossy()
+            .replace('-', "_"))
+    } else {
+        Ok(name.replace('-', "_"))
+    }
+}
+
+fn copy_into(file: &Path, dest_dir: &Path) -> Result<()> {
+    let file_name = file
+        .file_name()
+        .with_context(|| format!("{} has no file name", file.display()))?;
+    fs::copy(file, dest_dir.join(file_name))
+        .with_context(|| format!("Failed to install {}", file.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pkgid;
+
+    #[test]
+    fn parse_pkgid_with_explicit_name() {
+        let name = parse_pkgid("file:///home/user/my-crate#my-crate@1.0.0").unwrap();
+        assert_eq!(name, "my_crate");
+    }
+
+    #[test]
+    fn parse_pkgid_without_name_component() {
+        let name = parse_pkgid("file:///home/user/my-crate#1.0.0").unwrap();
+        assert_eq!(name, "my_crate");
+    }
+
+    #[test]
+    fn parse_pkgid_rejects_missing_path() {
+        assert!(parse_pkgid("#1.0.0").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use clap::Parser;
+
+mod build;
+mod cbuild;
+mod msvc;
+mod toolchain;
+
+use build::Build;
+use cbuild::{Cbuild, Cinstall};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+enum Opt {
+    #[clap(subcommand)]
+    Xwinbuild(Subcommand),
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Subcommand {
+    /// Compile a local package and all of its dependencies
+    Build(Build),
+    /// Compile a local package into a consumable Windows C library
+    Cbuild(Cbuild),
+    /// Build and install a Windows C library under a prefix
+    Cinstall(Cinstall),
+}
+
+fn main() -> Result<()> {
+    let Opt::Xwinbuild(subcommand) = Opt::parse();
+    match subcommand {
+        Subcommand::Build(build) => build.execute(),
+        Subcommand::Cbuild(cbuild) => cbuild.execute(),
+        Subcommand::Cinstall(cinstall) => cinstall.execute(),
+    }
+}
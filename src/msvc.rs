@@ -0,0 +1,188 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use xwin::Arch;
+
+/// An MSVC CRT + Windows SDK installation already present on the host,
+/// found instead of downloading one via xwin.
+#[derive(Clone, Debug)]
+pub struct SystemMsvc {
+    pub include_dirs: Vec<PathBuf>,
+    pub lib_dirs: Vec<PathBuf>,
+}
+
+impl SystemMsvc {
+    /// `/imsvc<dir>` flags for `CL_FLAGS`/`CFLAGS_*`/`CXXFLAGS_*`
+    pub fn cl_flags(&self) -> String {
+        self.include_dirs
+            .iter()
+            .map(|dir| format!("/imsvc{}", dir.display()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `-Lnative=<dir>` flags for `CARGO_TARGET_*_RUSTFLAGS`
+    pub fn rustflags(&self) -> String {
+        self.lib_dirs
+            .iter()
+            .map(|dir| format!("-Lnative={}", dir.display()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Detect an already-installed MSVC/SDK toolchain for `arch`, honoring an explicit
+/// `--msvc-root`, `VCINSTALLDIR`/`WindowsSdkDir`, and (on Windows) `vswhere`.
+pub fn detect(msvc_root: Option<&Path>, arch: Arch) -> Option<SystemMsvc> {
+    if let Some(root) = msvc_root {
+        if let Some(msvc) = from_root(root, arch) {
+            return Some(msvc);
+        }
+    }
+
+    if let (Ok(vc_dir), Ok(sdk_dir)) = (env::var("VCINSTALLDIR"), env::var("WindowsSdkDir")) {
+        if let Some(msvc) = from_env_vars(Path::new(&vc_dir), Path::new(&sdk_dir), arch) {
+            return Some(msvc);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(vs_root) = locate_via_vswhere() {
+        if let Some(msvc) = from_root(&vs_root, arch) {
+            return Some(msvc);
+        }
+    }
+
+    None
+}
+
+fn arch_name(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => "x86",
+        Arch::X86_64 => "x64",
+        Arch::Aarch => "arm",
+        Arch::Aarch64 => "arm64",
+    }
+}
+
+/// Build a [`SystemMsvc`] from an install root laid out like a Visual Studio
+/// installation (`VC/Tools/MSVC/<ver>/...` and a sibling Windows Kits SDK).
+fn from_root(root: &Path, arch: Arch) -> Option<SystemMsvc> {
+    let msvc_tools = root.join("VC").join("Tools").join("MSVC");
+    let msvc_version = fs_err::read_dir(&msvc_tools)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .max()?;
+
+    let sdk_root = find_windows_kits(Some(root))?;
+    from_dirs(&msvc_version, &sdk_root, arch)
+}
+
+fn from_env_vars(vc_dir: &Path, sdk_dir: &Path, arch: Arch) -> Option<SystemMsvc> {
+    from_dirs(vc_dir, sdk_dir, arch)
+}
+
+fn from_dirs(msvc_dir: &Path, sdk_root: &Path, arch: Arch) -> Option<SystemMsvc> {
+    let msvc_include = msvc_dir.join("include");
+    let msvc_lib = msvc_dir.join("lib").join(arch_name(arch));
+    if !msvc_include.is_dir() || !msvc_lib.is_dir() {
+        return None;
+    }
+
+    let sdk_include = sdk_root.join("Include");
+    let sdk_version = fs_err::read_dir(&sdk_include)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .max()?;
+    let sdk_lib = sdk_root.join("Lib").join(
+        sdk_version
+            .file_name()
+            .expect("sdk version directory has a name"),
+    );
+
+    let include_dirs = vec![
+        msvc_include,
+        sdk_version.join("ucrt"),
+        sdk_version.join("um"),
+        sdk_version.join("shared"),
+    ];
+    let lib_dirs = vec![
+        msvc_lib,
+        sdk_lib.join("ucrt").join(arch_name(arch)),
+        sdk_lib.join("um").join(arch_name(arch)),
+    ];
+
+    if include_dirs.iter().all(|dir| dir.is_dir()) && lib_dirs.iter().all(|dir| dir.is_dir()) {
+        Some(SystemMsvc {
+            include_dirs,
+            lib_dirs,
+        })
+    } else {
+        None
+    }
+}
+
+/// Locate a Windows Kits SDK directory, optionally rooted at an explicit
+/// `--msvc-root` (which may bundle its own SDK copy alongside `VC/`, the way
+/// an offline mirror of a Visual Studio install typically does).
+fn find_windows_kits(root: Option<&Path>) -> Option<PathBuf> {
+    if let Some(root) = root {
+        for candidate in [
+            root.join("Windows Kits").join("10"),
+            root.join("Windows Kits").join("11"),
+        ] {
+            if candidate.is_dir() {
+                return Some(candidate);
+            }
+        }
+    }
+
+    env::var("WindowsSdkDir")
+        .map(PathBuf::from)
+        .ok()
+        .filter(|p| p.is_dir())
+        .or_else(|| {
+            let candidate = PathBuf::from(r"C:\Program Files (x86)\Windows Kits\10");
+            candidate.is_dir().then_some(candidate)
+        })
+}
+
+/// Locate the newest Visual Studio install via `vswhere.exe`, mirroring the
+/// approach the `cc` crate's `windows/vs_instances.rs` uses.
+#[cfg(target_os = "windows")]
+fn locate_via_vswhere() -> Option<PathBuf> {
+    use std::process::Command;
+
+    let program_files =
+        env::var("ProgramFiles(x86)").or_else(|_| env::var("ProgramFiles")).ok()?;
+    let vswhere = Path::new(&program_files)
+        .join("Microsoft Visual Studio")
+        .join("Installer")
+        .join("vswhere.exe");
+    if !vswhere.is_file() {
+        return None;
+    }
+
+    let output = Command::new(vswhere)
+        .args([
+            "-latest",
+            "-products",
+            "*",
+            "-requires",
+            "Microsoft.VisualStudio.Component.VC.Tools.x86.x64",
+            "-property",
+            "installationPath",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = String::from_utf8(output.stdout).ok()?;
+    let path = path.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
@@ -0,0 +1,212 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use fs_err as fs;
+
+/// Minimum LLVM version known to ship `clang-cl`/`lld-link`/`llvm-lib`/`llvm-dlltool`
+/// with the features this crate relies on.
+const MIN_LLVM_VERSION: u32 = 15;
+
+/// Pinned LLVM release used by `--download-llvm`
+const LLVM_RELEASE_VERSION: &str = "17.0.6";
+
+/// The LLVM tools needed to build for `*-pc-windows-msvc` targets
+#[derive(Clone, Debug)]
+pub struct Toolchain {
+    pub clang_cl: PathBuf,
+    pub lld_link: PathBuf,
+    pub llvm_lib: PathBuf,
+    pub llvm_dlltool: PathBuf,
+    /// Directory all four tools were found in, to be prepended to `PATH`
+    pub bin_dir: PathBuf,
+}
+
+impl Toolchain {
+    /// The current process `PATH` with this toolchain's `bin` directory prepended,
+    /// for spawning other LLVM tools (e.g. `llvm-readobj`) that aren't tracked here
+    pub fn env_path(&self) -> Result<std::ffi::OsString> {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let mut dirs = vec![self.bin_dir.clone()];
+        dirs.extend(env::split_paths(&path));
+        Ok(env::join_paths(dirs)?)
+    }
+}
+
+/// Locate a working LLVM toolchain, optionally downloading a pinned release into
+/// `cache_dir` if none is found and `download` is set.
+pub fn discover(cache_dir: &Path, download: bool) -> Result<Toolchain> {
+    for dir in candidate_dirs(cache_dir) {
+        if let Some(toolchain) = probe_dir(&dir) {
+            return Ok(toolchain);
+        }
+    }
+
+    if download {
+        let bin_dir = download_llvm(cache_dir)?;
+        if let Some(toolchain) = probe_dir(&bin_dir) {
+            return Ok(toolchain);
+        }
+        bail!(
+            "downloaded LLVM {} into {} but it did not contain a usable clang-cl/lld-link/llvm-lib/llvm-dlltool",
+            LLVM_RELEASE_VERSION,
+            bin_dir.display()
+        );
+    }
+
+    bail!(
+        "could not find clang-cl, lld-link, llvm-lib and llvm-dlltool on PATH or in common LLVM \
+         install locations. Install LLVM >= {} (e.g. `brew install llvm` or `apt install llvm`), \
+         set XWIN_LLVM_DIR/LLVM_PATH to its `bin` directory, or re-run with --download-llvm",
+        MIN_LLVM_VERSION
+    )
+}
+
+/// Directories to look for an LLVM `bin` directory in, in priority order
+fn candidate_dirs(cache_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for var in ["XWIN_LLVM_DIR", "LLVM_PATH"] {
+        if let Ok(dir) = env::var(var) {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+
+    if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/opt/homebrew/opt/llvm/bin"));
+        dirs.push(PathBuf::from("/usr/local/opt/llvm/bin"));
+    }
+    if cfg!(target_os = "linux") {
+        dirs.push(PathBuf::from("/usr/lib/llvm/bin"));
+        for version in (MIN_LLVM_VERSION..=20).rev() {
+            dirs.push(PathBuf::from(format!("/usr/lib/llvm-{}/bin", version)));
+        }
+        dirs.push(PathBuf::from("/usr/bin"));
+    }
+
+    if let Ok(path) = env::var("PATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+
+    dirs.push(llvm_download_dir(cache_dir).join("bin"));
+    dirs
+}
+
+fn probe_dir(dir: &Path) -> Option<Toolchain> {
+    let clang_cl = dir.join(exe_name("clang-cl"));
+    let lld_link = dir.join(exe_name("lld-link"));
+    let llvm_lib = dir.join(exe_name("llvm-lib"));
+    let llvm_dlltool = dir.join(exe_name("llvm-dlltool"));
+
+    if [&clang_cl, &lld_link, &llvm_lib, &llvm_dlltool]
+        .iter()
+        .all(|tool| tool.is_file() && tool_runs(tool))
+    {
+        Some(Toolchain {
+            clang_cl,
+            lld_link,
+            llvm_lib,
+            llvm_dlltool,
+            bin_dir: dir.to_path_buf(),
+        })
+    } else {
+        None
+    }
+}
+
+fn tool_runs(tool: &Path) -> bool {
+    Command::new(tool)
+        .arg("--version")
+        .output()
+        .map(|out| out.status.success())
+        .unwrap_or(false)
+}
+
+fn exe_name(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn llvm_download_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("llvm").join(LLVM_RELEASE_VERSION)
+}
+
+/// Download and unpack a pinned prebuilt LLVM release into the xwin cache dir,
+/// mirroring how `setup_msvc_crt` populates the cache. Returns the `bin` directory.
+fn download_llvm(cache_dir: &Path) -> Result<PathBuf> {
+    let dest = llvm_download_dir(cache_dir);
+    let bin_dir = dest.join("bin");
+    if probe_dir(&bin_dir).is_some() {
+        return Ok(bin_dir);
+    }
+    fs::create_dir_all(&dest)?;
+
+    let (asset, strip_components) = llvm_release_asset()?;
+    let url = format!(
+        "https://github.com/llvm/llvm-project/releases/download/llvmorg-{}/{}",
+        LLVM_RELEASE_VERSION, asset
+    );
+    let archive = dest.join(&asset);
+
+    let status = Command::new("curl")
+        .args(["-L", "--fail", "-o"])
+        .arg(&archive)
+        .arg(&url)
+        .status()
+        .context("Failed to run curl to download LLVM")?;
+    if !status.success() {
+        bail!("Failed to download LLVM release from {}", url);
+    }
+
+    let status = Command::new("tar")
+        .arg("xf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&dest)
+        .arg("--strip-components")
+        .arg(strip_components.to_string())
+        .status()
+        .context("Failed to run tar to unpack LLVM")?;
+    if !status.success() {
+        bail!("Failed to unpack {}", archive.display());
+    }
+    let _ = fs::remove_file(&archive);
+
+    Ok(bin_dir)
+}
+
+fn llvm_release_asset() -> Result<(String, u32)> {
+    let triple = match (env::consts::OS, env::consts::ARCH) {
+        ("macos", "aarch64") => "arm64-apple-darwin22.0",
+        ("macos", "x86_64") => "x86_64-apple-darwin22.0",
+        ("linux", "x86_64") => "x86_64-linux-gnu-ubuntu-22.04",
+        (os, arch) => bail!("--download-llvm has no pinned release for {}-{}", arch, os),
+    };
+    Ok((
+        format!("clang+llvm-{}-{}.tar.xz", LLVM_RELEASE_VERSION, triple),
+        1,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{candidate_dirs, llvm_download_dir, probe_dir};
+
+    #[test]
+    fn candidate_dirs_ends_with_cache_dir_fallback() {
+        let cache_dir = std::env::temp_dir().join("cargo-xwinbuild-test-cache");
+        let dirs = candidate_dirs(&cache_dir);
+        assert_eq!(dirs.last(), Some(&llvm_download_dir(&cache_dir).join("bin")));
+    }
+
+    #[test]
+    fn probe_dir_returns_none_when_tools_are_missing() {
+        let dir = std::env::temp_dir().join("cargo-xwinbuild-test-empty-toolchain-dir");
+        let _ = std::fs::create_dir_all(&dir);
+        assert!(probe_dir(&dir).is_none());
+    }
+}